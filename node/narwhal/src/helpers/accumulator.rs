@@ -0,0 +1,207 @@
+// Copyright (C) 2019-2023 Aleo Systems Inc.
+// This file is part of the snarkOS library.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at:
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use snarkvm::{console::types::Field, prelude::Network};
+
+/// An inclusion proof that a leaf was committed at a particular index of a [`CertificateAccumulator`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccumulatorProof<N: Network> {
+    /// The index of the leaf that this proof is for.
+    leaf_index: usize,
+    /// The sibling hash at each layer, from the leaves up to (but excluding) the root.
+    siblings: Vec<Field<N>>,
+}
+
+impl<N: Network> AccumulatorProof<N> {
+    /// Returns the index of the leaf that this proof is for.
+    pub const fn leaf_index(&self) -> usize {
+        self.leaf_index
+    }
+
+    /// Returns the sibling hashes, from the leaves up to (but excluding) the root.
+    pub fn siblings(&self) -> &[Field<N>] {
+        &self.siblings
+    }
+}
+
+/// An incremental, append-only Merkle accumulator over the IDs of committed `BatchCertificate`s.
+///
+/// The accumulator is organized as a vector of layers, where layer `0` holds the leaf hashes (in
+/// commit order) and each higher layer holds the hash of each adjacent pair of nodes in the layer
+/// below. When a layer has an odd number of nodes, the last node is paired with itself to derive
+/// its (tentative) parent; that parent is recomputed once the node's real sibling is appended.
+#[derive(Clone, Debug)]
+pub struct CertificateAccumulator<N: Network> {
+    /// The layers of the accumulator, from the leaves (layer `0`) up to the root.
+    layers: Vec<Vec<Field<N>>>,
+}
+
+impl<N: Network> Default for CertificateAccumulator<N> {
+    /// Initializes a new, empty accumulator.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: Network> CertificateAccumulator<N> {
+    /// Initializes a new, empty accumulator.
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    /// Returns the number of leaves that have been appended to the accumulator.
+    pub fn len(&self) -> usize {
+        self.layers.first().map_or(0, |layer| layer.len())
+    }
+
+    /// Returns `true` if the accumulator contains no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the current root of the accumulator.
+    /// Note: the root of an empty accumulator is `Field::zero()`.
+    pub fn root(&self) -> Field<N> {
+        match self.layers.last() {
+            Some(layer) => layer[0],
+            None => Field::zero(),
+        }
+    }
+
+    /// Appends a new leaf to the accumulator, updating the affected right spine of the tree.
+    pub fn append(&mut self, leaf: Field<N>) {
+        if self.layers.is_empty() {
+            self.layers.push(Vec::new());
+        }
+        self.layers[0].push(leaf);
+
+        // Walk upward from the leaves, recomputing only the nodes on the path of the new leaf.
+        let mut level = 0;
+        while self.layers[level].len() > 1 {
+            let index = self.layers[level].len() - 1;
+            let node = self.layers[level][index];
+
+            // Determine the pairing for the parent node, duplicating the node if it has no sibling yet.
+            let (left, right) = match index % 2 == 0 {
+                true => (node, node),
+                false => (self.layers[level][index - 1], node),
+            };
+            let parent = Self::hash_pair(left, right);
+            let parent_index = index / 2;
+
+            if self.layers.len() == level + 1 {
+                self.layers.push(Vec::new());
+            }
+            match parent_index < self.layers[level + 1].len() {
+                true => self.layers[level + 1][parent_index] = parent,
+                false => self.layers[level + 1].push(parent),
+            }
+
+            level += 1;
+        }
+    }
+
+    /// Returns an inclusion proof for the leaf at the given `index`, as of the current root.
+    pub fn prove(&self, index: usize) -> Option<AccumulatorProof<N>> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut siblings = Vec::new();
+        let mut current_index = index;
+        for level in 0..self.layers.len().saturating_sub(1) {
+            let layer = &self.layers[level];
+            let sibling_index = current_index ^ 1;
+            // If there is no sibling (odd node out), the node was duplicated as its own sibling.
+            siblings.push(layer.get(sibling_index).copied().unwrap_or(layer[current_index]));
+            current_index /= 2;
+        }
+        Some(AccumulatorProof { leaf_index: index, siblings })
+    }
+
+    /// Returns `true` if `proof` proves that `leaf` was committed at its recorded index under `root`.
+    pub fn verify(leaf: Field<N>, proof: &AccumulatorProof<N>, root: Field<N>) -> bool {
+        let mut index = proof.leaf_index;
+        let mut node = leaf;
+        for sibling in &proof.siblings {
+            node = match index % 2 == 0 {
+                true => Self::hash_pair(node, *sibling),
+                false => Self::hash_pair(*sibling, node),
+            };
+            index /= 2;
+        }
+        node == root
+    }
+
+    /// Hashes a pair of adjacent nodes into their parent node.
+    fn hash_pair(left: Field<N>, right: Field<N>) -> Field<N> {
+        N::hash_psd2(&[left, right]).expect("Failed to hash Merkle accumulator node")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snarkvm::{prelude::Testnet3, utilities::TestRng};
+
+    type CurrentNetwork = Testnet3;
+
+    fn sample_leaves(n: usize, rng: &mut TestRng) -> Vec<Field<CurrentNetwork>> {
+        (0..n).map(|_| Field::rand(rng)).collect()
+    }
+
+    #[test]
+    fn test_empty_root() {
+        let accumulator = CertificateAccumulator::<CurrentNetwork>::new();
+        assert!(accumulator.is_empty());
+        assert_eq!(accumulator.root(), Field::zero());
+    }
+
+    #[test]
+    fn test_single_leaf_root_is_the_leaf() {
+        let mut rng = TestRng::default();
+        let leaf = sample_leaves(1, &mut rng)[0];
+
+        let mut accumulator = CertificateAccumulator::<CurrentNetwork>::new();
+        accumulator.append(leaf);
+        assert_eq!(accumulator.len(), 1);
+        assert_eq!(accumulator.root(), leaf);
+    }
+
+    #[test]
+    fn test_append_and_prove() {
+        let mut rng = TestRng::default();
+        // Use an odd number of leaves to exercise the duplicate-node path.
+        let leaves = sample_leaves(5, &mut rng);
+
+        let mut accumulator = CertificateAccumulator::<CurrentNetwork>::new();
+        for leaf in &leaves {
+            accumulator.append(*leaf);
+        }
+        let root = accumulator.root();
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            let proof = accumulator.prove(i).expect("proof should exist for a committed leaf");
+            assert_eq!(proof.leaf_index(), i);
+            assert!(CertificateAccumulator::verify(*leaf, &proof, root));
+        }
+
+        // A proof should not verify against a different leaf.
+        let bad_proof = accumulator.prove(0).unwrap();
+        assert!(!CertificateAccumulator::verify(leaves[1], &bad_proof, root));
+
+        // There is no proof for an index that hasn't been appended yet.
+        assert!(accumulator.prove(leaves.len()).is_none());
+    }
+}
@@ -12,39 +12,118 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::{
+    accumulator::{AccumulatorProof, CertificateAccumulator},
+    storage::{DagStore, NoopDagStore},
+};
+use anyhow::Result;
 use snarkvm::{
     console::types::{Address, Field},
     ledger::narwhal::BatchCertificate,
     prelude::Network,
 };
 
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    sync::Arc,
+};
+
+/// The certificate(s) submitted by a single author within a single round.
+///
+/// In the honest case, an author submits exactly one certificate per round, which is held in
+/// `accepted`. If the author equivocates — publishing a second, distinct certificate in the same
+/// round — the conflicting certificate(s) are retained in `conflicts` as slashing evidence, rather
+/// than silently overwriting `accepted`. `conflicts` is empty (and allocation-free) for every
+/// honest author.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorCertificates<N: Network> {
+    /// The first certificate accepted from this author in this round.
+    accepted: BatchCertificate<N>,
+    /// Any further, conflicting certificates seen from this author in this round.
+    conflicts: Vec<BatchCertificate<N>>,
+}
+
+impl<N: Network> AuthorCertificates<N> {
+    /// Initializes a new entry with no recorded equivocations.
+    pub(crate) fn new(accepted: BatchCertificate<N>) -> Self {
+        Self { accepted, conflicts: Vec::new() }
+    }
+
+    /// Records `certificate` as a conflicting certificate, if it isn't already known.
+    pub(crate) fn record_conflict(&mut self, certificate: BatchCertificate<N>) {
+        if self.accepted.certificate_id() != certificate.certificate_id()
+            && !self.conflicts.iter().any(|conflict| conflict.certificate_id() == certificate.certificate_id())
+        {
+            self.conflicts.push(certificate);
+        }
+    }
+}
 
-#[derive(Debug)]
 pub struct DAG<N: Network> {
     /// The in-memory collection of certificates that comprise the DAG.
-    graph: BTreeMap<u64, HashMap<Address<N>, BatchCertificate<N>>>,
+    graph: BTreeMap<u64, HashMap<Address<N>, AuthorCertificates<N>>>,
     /// The last round that was committed.
     last_committed_round: u64,
     /// The last authors that were committed, along with the round they were committed in.
     last_committed_authors: HashMap<Address<N>, u64>,
+    /// The append-only Merkle accumulator over the IDs of committed certificates, in commit order.
+    accumulator: CertificateAccumulator<N>,
+    /// The persistence backend that every `insert` and `commit` is write-ahead-logged to.
+    /// Defaults to a no-op store, so the DAG is purely in-memory unless `with_store` or `open` is used.
+    store: Arc<dyn DagStore<N>>,
+}
+
+impl<N: Network> std::fmt::Debug for DAG<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DAG")
+            .field("graph", &self.graph)
+            .field("last_committed_round", &self.last_committed_round)
+            .field("last_committed_authors", &self.last_committed_authors)
+            .field("accumulator", &self.accumulator)
+            .finish()
+    }
 }
 
 impl<N: Network> Default for DAG<N> {
-    /// Initializes a new DAG.
+    /// Initializes a new, purely in-memory DAG.
     fn default() -> Self {
         Self::new()
     }
 }
 
 impl<N: Network> DAG<N> {
-    /// Initializes a new DAG.
+    /// Initializes a new, purely in-memory DAG (persistence is a no-op).
     pub fn new() -> Self {
-        Self { graph: Default::default(), last_committed_round: 0, last_committed_authors: Default::default() }
+        Self::with_store(Arc::new(NoopDagStore))
+    }
+
+    /// Initializes a new, empty DAG backed by `store`.
+    /// Use `open` instead to reconstruct a DAG from a store's existing persisted state.
+    pub fn with_store(store: Arc<dyn DagStore<N>>) -> Self {
+        Self {
+            graph: Default::default(),
+            last_committed_round: 0,
+            last_committed_authors: Default::default(),
+            accumulator: Default::default(),
+            store,
+        }
+    }
+
+    /// Reconstructs a DAG from `store`'s persisted state, replaying its graph, last committed round,
+    /// last committed authors, and the Merkle accumulator over committed certificates.
+    pub fn open(store: Arc<dyn DagStore<N>>) -> Result<Self> {
+        let (graph, last_committed_round, last_committed_authors, accumulator_leaves) = store.load()?;
+
+        let mut accumulator = CertificateAccumulator::default();
+        for leaf in accumulator_leaves {
+            accumulator.append(leaf);
+        }
+
+        Ok(Self { graph, last_committed_round, last_committed_authors, accumulator, store })
     }
 
     /// Returns the DAG.
-    pub const fn graph(&self) -> &BTreeMap<u64, HashMap<Address<N>, BatchCertificate<N>>> {
+    pub const fn graph(&self) -> &BTreeMap<u64, HashMap<Address<N>, AuthorCertificates<N>>> {
         &self.graph
     }
 
@@ -58,16 +137,32 @@ impl<N: Network> DAG<N> {
         &self.last_committed_authors
     }
 
+    /// Returns the current root of the Merkle accumulator over committed certificates.
+    pub fn accumulator_root(&self) -> Field<N> {
+        self.accumulator.root()
+    }
+
+    /// Returns an inclusion proof that the certificate committed at `index` (in commit order) is
+    /// included under the current accumulator root.
+    pub fn prove_committed(&self, index: usize) -> Option<AccumulatorProof<N>> {
+        self.accumulator.prove(index)
+    }
+
     /// Returns `true` if the given certificate ID exists in the given round.
     pub fn contains_certificate_in_round(&self, round: u64, certificate_id: Field<N>) -> bool {
-        self.graph
-            .get(&round)
-            .map_or(false, |map| map.values().any(|certificate| certificate.certificate_id() == certificate_id))
+        self.graph.get(&round).map_or(false, |map| {
+            map.values().any(|entry| {
+                entry.accepted.certificate_id() == certificate_id
+                    || entry.conflicts.iter().any(|conflict| conflict.certificate_id() == certificate_id)
+            })
+        })
     }
 
-    /// Returns the batch certificate for the given round and author.
+    /// Returns the accepted batch certificate for the given round and author.
+    /// Note: if the author equivocated in this round, this returns only the first-accepted certificate;
+    /// use `equivocations` to retrieve the conflicting certificate(s).
     pub fn get_certificate_for_round_with_author(&self, round: u64, author: Address<N>) -> Option<BatchCertificate<N>> {
-        self.graph.get(&round).and_then(|certificates| certificates.get(&author)).cloned()
+        self.graph.get(&round).and_then(|certificates| certificates.get(&author)).map(|entry| entry.accepted.clone())
     }
 
     /// Returns the batch certificate for the given round and certificate ID.
@@ -76,32 +171,66 @@ impl<N: Network> DAG<N> {
         round: u64,
         certificate_id: Field<N>,
     ) -> Option<BatchCertificate<N>> {
+        self.graph.get(&round).and_then(|map| {
+            map.values().find_map(|entry| {
+                match entry.accepted.certificate_id() == certificate_id {
+                    true => Some(&entry.accepted),
+                    false => entry.conflicts.iter().find(|conflict| conflict.certificate_id() == certificate_id),
+                }
+            })
+        }).cloned()
+    }
+
+    /// Returns the accepted batch certificates for the given round.
+    pub fn get_certificates_for_round(&self, round: u64) -> Option<HashMap<Address<N>, BatchCertificate<N>>> {
         self.graph
             .get(&round)
-            .and_then(|map| map.values().find(|certificate| certificate.certificate_id() == certificate_id))
-            .cloned()
+            .map(|certificates| certificates.iter().map(|(address, entry)| (*address, entry.accepted.clone())).collect())
     }
 
-    /// Returns the batch certificates for the given round.
-    pub fn get_certificates_for_round(&self, round: u64) -> Option<HashMap<Address<N>, BatchCertificate<N>>> {
-        self.graph.get(&round).cloned()
+    /// Returns the equivocation evidence collected so far, as `(round, author, accepted, conflicting)` tuples.
+    pub fn equivocations(&self) -> Vec<(u64, Address<N>, BatchCertificate<N>, BatchCertificate<N>)> {
+        self.graph
+            .iter()
+            .flat_map(|(round, authors)| {
+                authors.iter().flat_map(move |(author, entry)| {
+                    entry.conflicts.iter().map(move |conflict| (*round, *author, entry.accepted.clone(), conflict.clone()))
+                })
+            })
+            .collect()
     }
 
     /// Inserts a certificate into the DAG.
-    pub fn insert(&mut self, certificate: BatchCertificate<N>) {
+    /// If the author has already submitted a different certificate in this round, the new certificate
+    /// is preserved alongside the first as equivocation evidence, rather than overwriting it.
+    pub fn insert(&mut self, certificate: BatchCertificate<N>) -> Result<()> {
+        // Write the certificate ahead to the persistence backend before applying it in-memory.
+        self.store.insert(&certificate)?;
+
         let round = certificate.round();
         let author = certificate.author();
-        // Insert the certificate into the DAG.
-        self.graph.entry(round).or_default().insert(author, certificate);
+
+        match self.graph.entry(round).or_default().entry(author) {
+            // The fast path: no certificate recorded yet for this author in this round.
+            Entry::Vacant(entry) => {
+                entry.insert(AuthorCertificates::new(certificate));
+            }
+            // The author has already submitted a certificate in this round; check for equivocation.
+            Entry::Occupied(mut entry) => entry.get_mut().record_conflict(certificate),
+        }
+        Ok(())
     }
 
     /// Commits a certificate, removing all certificates for this author at or before this round from the DAG.
-    pub fn commit(&mut self, certificate: BatchCertificate<N>, max_gc_rounds: u64) {
+    pub fn commit(&mut self, certificate: BatchCertificate<N>, max_gc_rounds: u64) -> Result<()> {
         let certificate_round = certificate.round();
         let author = certificate.author();
+        let certificate_id = certificate.certificate_id();
 
-        // Update the last committed round for the author.
-        self.last_committed_authors
+        // Compute the updated commit state without mutating `self` yet, so that if the store write
+        // below fails, the in-memory DAG (including the accumulator) is left exactly as it was.
+        let mut last_committed_authors = self.last_committed_authors.clone();
+        last_committed_authors
             .entry(author)
             .and_modify(|last_committed_round| {
                 if certificate_round > *last_committed_round {
@@ -109,12 +238,21 @@ impl<N: Network> DAG<N> {
                 }
             })
             .or_insert(certificate_round);
-
-        // Update the last committed round.
         // Note: The '.unwrap()' here is guaranteed to be safe.
-        self.last_committed_round = *self.last_committed_authors.values().max().unwrap();
+        let last_committed_round = *last_committed_authors.values().max().unwrap();
+
+        // Write the commit ahead to the persistence backend, atomically with its GC deletions,
+        // before applying any of it in-memory.
+        self.store.commit(author, certificate_round, certificate_id, max_gc_rounds, last_committed_round, &last_committed_authors)?;
+
+        // The store write succeeded; it is now safe to apply the same updates in-memory.
+        self.accumulator.append(certificate_id);
+        self.last_committed_authors = last_committed_authors;
+        self.last_committed_round = last_committed_round;
 
         // Remove certificates that are below the GC round.
+        // Note: since equivocation evidence is stored alongside the accepted certificate, this also
+        // purges any stale equivocation records for rounds below the GC horizon.
         self.graph.retain(|round, _| round + max_gc_rounds > self.last_committed_round);
         // Remove any certificates for this author that are at or below the certificate round.
         self.graph.retain(|round, map| match *round > certificate_round {
@@ -124,6 +262,7 @@ impl<N: Network> DAG<N> {
                 !map.is_empty()
             }
         });
+        Ok(())
     }
 }
 
@@ -150,7 +289,7 @@ mod tests {
         let mut dag = DAG::<Testnet3>::new();
 
         let certificate = sample_batch_certificate(&mut rng);
-        dag.insert(certificate.clone());
+        dag.insert(certificate.clone()).unwrap();
         let round = certificate.round();
         assert!(dag.contains_certificate_in_round(round, certificate.certificate_id()));
         assert_eq!(dag.get_certificate_for_round_with_author(round, certificate.author()), Some(certificate.clone()));
@@ -160,10 +299,14 @@ mod tests {
         );
         assert_eq!(
             dag.get_certificates_for_round(round),
-            Some(vec![(certificate.author(), certificate)].into_iter().collect())
+            Some(vec![(certificate.author(), certificate.clone())].into_iter().collect())
         );
         assert_eq!(dag.last_committed_round(), 0);
         assert_eq!(dag.last_committed_authors().len(), 0);
+
+        // Re-inserting the same certificate is not an equivocation: no conflict is recorded.
+        dag.insert(certificate).unwrap();
+        assert!(dag.equivocations().is_empty());
     }
 
     #[test]
@@ -172,7 +315,7 @@ mod tests {
         let mut dag = DAG::<Testnet3>::new();
 
         let certificate = sample_batch_certificate(&mut rng);
-        dag.insert(certificate.clone());
+        dag.insert(certificate.clone()).unwrap();
         let round = certificate.round();
         assert!(dag.contains_certificate_in_round(round, certificate.certificate_id()));
         assert_eq!(dag.get_certificate_for_round_with_author(round, certificate.author()), Some(certificate.clone()));
@@ -188,9 +331,14 @@ mod tests {
         assert_eq!(dag.last_committed_authors().len(), 0);
 
         // now commit the certificate, this will trigger GC
-        dag.commit(certificate.clone(), 10);
+        dag.commit(certificate.clone(), 10).unwrap();
         assert!(!dag.contains_certificate_in_round(round, certificate.certificate_id()));
         assert_eq!(dag.last_committed_round(), round);
         assert_eq!(dag.last_committed_authors().len(), 1);
+
+        // The Merkle accumulator should now contain the committed certificate as its sole leaf.
+        assert_eq!(dag.accumulator_root(), certificate.certificate_id());
+        let proof = dag.prove_committed(0).expect("a proof should exist for the committed certificate");
+        assert!(CertificateAccumulator::verify(certificate.certificate_id(), &proof, dag.accumulator_root()));
     }
 }
\ No newline at end of file
@@ -12,31 +12,55 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use parking_lot::RwLock;
+use futures::stream::Stream;
+use parking_lot::{Mutex, RwLock};
 use std::{
     collections::{HashMap, HashSet},
     hash::Hash,
     net::SocketAddr,
+    pin::Pin,
     sync::Arc,
+    task::{Context, Poll, Waker},
+    time::Duration,
 };
+use tokio_util::time::{delay_queue, DelayQueue};
+
+/// The default time-to-live for a pending `(transmission ID, peer IP)` request.
+const DEFAULT_PENDING_TTL: Duration = Duration::from_secs(5);
 
 #[derive(Clone, Debug)]
-pub struct Pending<T: PartialEq + Eq + Hash> {
+pub struct Pending<T: Clone + PartialEq + Eq + Hash> {
     /// The map of pending `transmission IDs` to `peer IPs` that have the transmission.
     pending: Arc<RwLock<HashMap<T, HashSet<SocketAddr>>>>,
+    /// The time-to-live for a pending `(transmission ID, peer IP)` request, after which it is considered expired.
+    ttl: Duration,
+    /// The delay queue that schedules the expiry of each `(transmission ID, peer IP)` request.
+    /// Note: the global lock order is `delay_keys` before `delays` - see `refresh_expiry`/`cancel_expiry`/`poll_next`.
+    delays: Arc<RwLock<DelayQueue<(T, SocketAddr)>>>,
+    /// The map of `(transmission ID, peer IP)` requests to their key in the delay queue.
+    delay_keys: Arc<RwLock<HashMap<(T, SocketAddr), delay_queue::Key>>>,
+    /// The waker parked by `poll_next` on each poll, so that the next `insert` can
+    /// wake the reaper back up instead of leaving the stream stalled forever.
+    waker: Arc<Mutex<Option<Waker>>>,
 }
 
-impl<T: PartialEq + Eq + Hash> Default for Pending<T> {
+impl<T: Clone + PartialEq + Eq + Hash> Default for Pending<T> {
     /// Initializes a new instance of the pending queue.
     fn default() -> Self {
-        Self::new()
+        Self::new(DEFAULT_PENDING_TTL)
     }
 }
 
-impl<T: PartialEq + Eq + Hash> Pending<T> {
-    /// Initializes a new instance of the pending queue.
-    pub fn new() -> Self {
-        Self { pending: Default::default() }
+impl<T: Clone + PartialEq + Eq + Hash> Pending<T> {
+    /// Initializes a new instance of the pending queue, whose requests expire after the given `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            pending: Default::default(),
+            ttl,
+            delays: Arc::new(RwLock::new(DelayQueue::new())),
+            delay_keys: Default::default(),
+            waker: Default::default(),
+        }
     }
 
     /// Returns `true` if the pending queue is empty.
@@ -66,21 +90,109 @@ impl<T: PartialEq + Eq + Hash> Pending<T> {
 
     /// Inserts the specified `transmission ID` and `peer IP` to the pending queue.
     /// If the `transmission ID` already exists, the `peer IP` is added to the existing transmission.
+    /// In either case, the `(transmission ID, peer IP)` pairing is (re)scheduled to expire after the configured `ttl`.
     pub fn insert(&self, item: impl Into<T>, peer_ip: SocketAddr) {
-        self.pending.write().entry(item.into()).or_default().insert(peer_ip);
+        let item = item.into();
+        self.pending.write().entry(item.clone()).or_default().insert(peer_ip);
+        self.refresh_expiry(item, peer_ip);
     }
 
     /// Removes the specified `transmission ID` from the pending queue.
     /// If the `transmission ID` exists and is removed, `true` is returned.
     /// If the `transmission ID` does not exist, `false` is returned.
     pub fn remove(&self, item: impl Into<T>) -> bool {
-        self.pending.write().remove(&item.into()).is_some()
+        let item = item.into();
+        let removed = self.pending.write().remove(&item);
+        if let Some(peer_ips) = &removed {
+            for peer_ip in peer_ips {
+                self.cancel_expiry(&item, *peer_ip);
+            }
+        }
+        removed.is_some()
+    }
+
+    /// Schedules (or refreshes) the expiry of the given `(transmission ID, peer IP)` pairing.
+    fn refresh_expiry(&self, item: T, peer_ip: SocketAddr) {
+        let key = (item, peer_ip);
+        let mut delay_keys = self.delay_keys.write();
+        match delay_keys.get(&key) {
+            // If the pairing is already scheduled, reset its expiry.
+            Some(delay_key) => self.delays.write().reset(delay_key, self.ttl),
+            // Otherwise, schedule the pairing for expiry.
+            None => {
+                let delay_key = self.delays.write().insert(key.clone(), self.ttl);
+                delay_keys.insert(key, delay_key);
+            }
+        }
+        drop(delay_keys);
+
+        // The queue went from (possibly) empty to non-empty; wake `poll_next` if it parked itself.
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+
+    /// Cancels the scheduled expiry of the given `(transmission ID, peer IP)` pairing, if one exists.
+    fn cancel_expiry(&self, item: &T, peer_ip: SocketAddr) {
+        if let Some(delay_key) = self.delay_keys.write().remove(&(item.clone(), peer_ip)) {
+            self.delays.write().remove(&delay_key);
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + Eq + Hash + Unpin> Stream for Pending<T> {
+    type Item = (T, SocketAddr);
+
+    /// Polls for `(transmission ID, peer IP)` pairings that have expired, removing the peer IP
+    /// from the pending queue (and the transmission ID entirely, if it was the last remaining peer).
+    ///
+    /// The caller is expected to pick a different peer IP, via `get`, and re-request the transmission.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Park our waker *before* polling the delay queue, not after seeing an empty result: an
+        // empty `DelayQueue` registers no wake source of its own, so `refresh_expiry` is the only
+        // thing that can wake us once it inserts a new entry. Storing the waker first closes the
+        // gap between "the queue was observed empty" and "the waker slot is populated" - any
+        // `refresh_expiry` that lands before the poll below is reflected in the (now non-empty)
+        // queue the poll observes; any that lands after already finds our waker in place to take.
+        *self.waker.lock() = Some(cx.waker().clone());
+
+        // Bind the poll result to a local first, so the `delays` write guard is dropped before
+        // `delay_keys`/`pending` are touched below - matching the `delay_keys` -> `delays` lock
+        // order used by `refresh_expiry`/`cancel_expiry` and avoiding a lock-ordering deadlock.
+        let expired = self.delays.write().poll_expired(cx);
+        match expired {
+            Poll::Ready(Some(Ok(expired))) => {
+                let (item, peer_ip) = expired.into_inner();
+                self.delay_keys.write().remove(&(item.clone(), peer_ip));
+
+                // Remove the expired peer IP, and the transmission ID if it was the last remaining peer.
+                // Note: this is a no-op if the transmission ID was already fulfilled and removed.
+                let mut pending = self.pending.write();
+                if let Some(peer_ips) = pending.get_mut(&item) {
+                    peer_ips.remove(&peer_ip);
+                    if peer_ips.is_empty() {
+                        pending.remove(&item);
+                    }
+                }
+
+                Poll::Ready(Some((item, peer_ip)))
+            }
+            // A timer error should not tear down the stream; simply treat it as "nothing expired
+            // yet". The stream is polled again once a later `insert` wakes the waker parked above.
+            Poll::Ready(Some(Err(_))) => Poll::Pending,
+            // An empty delay queue reports `Ready(None)`, but the queue is refilled as new requests
+            // come in, so the stream itself must never be allowed to terminate. The waker parked
+            // above is `refresh_expiry`'s only way to wake us once a new entry is scheduled.
+            Poll::Ready(None) => Poll::Pending,
+            Poll::Pending => Poll::Pending,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures::StreamExt;
     use snarkvm::{
         ledger::{coinbase::PuzzleCommitment, narwhal::TransmissionID},
         prelude::{Rng, TestRng},
@@ -93,7 +205,10 @@ mod tests {
         let rng = &mut TestRng::default();
 
         // Initialize the ready queue.
-        let pending = Pending::<TransmissionID<CurrentNetwork>>::new();
+        // Scheduling an expiry on `insert` needs a Tokio timer, so enter a runtime for the test.
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let _guard = runtime.enter();
+        let pending = Pending::<TransmissionID<CurrentNetwork>>::new(DEFAULT_PENDING_TTL);
 
         // Check initially empty.
         assert!(pending.is_empty());
@@ -145,4 +260,32 @@ mod tests {
         // Check empty again.
         assert!(pending.is_empty());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_pending_expiry() {
+        let rng = &mut TestRng::default();
+
+        // Initialize a pending queue with a short TTL.
+        let mut pending = Pending::<TransmissionID<CurrentNetwork>>::new(Duration::from_millis(50));
+
+        let commitment = TransmissionID::Solution(PuzzleCommitment::from_g1_affine(rng.gen()));
+        let addr_1 = SocketAddr::from(([127, 0, 0, 1], 1234));
+        let addr_2 = SocketAddr::from(([127, 0, 0, 1], 2345));
+
+        // Insert two peers for the same transmission ID.
+        pending.insert(commitment, addr_1);
+        pending.insert(commitment, addr_2);
+        assert_eq!(pending.get(commitment), Some(HashSet::from([addr_1, addr_2])));
+
+        // The first peer to expire is yielded by the stream, but the transmission ID remains pending.
+        let (expired_item, expired_peer) = pending.next().await.unwrap();
+        assert_eq!(expired_item, commitment);
+        assert!(pending.contains(commitment));
+        assert!(!pending.contains_peer(commitment, expired_peer));
+
+        // The second (and final) peer expires, and the transmission ID is removed entirely.
+        let (expired_item, _) = pending.next().await.unwrap();
+        assert_eq!(expired_item, commitment);
+        assert!(!pending.contains(commitment));
+    }
+}
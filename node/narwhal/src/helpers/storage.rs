@@ -0,0 +1,359 @@
+// Copyright (C) 2019-2023 Aleo Systems Inc.
+// This file is part of the snarkOS library.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at:
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::dag::AuthorCertificates;
+use anyhow::{anyhow, Result};
+use snarkvm::{
+    console::types::{Address, Field},
+    ledger::narwhal::BatchCertificate,
+    prelude::Network,
+    utilities::{FromBytes, ToBytes},
+};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+    path::Path,
+};
+
+/// The reconstructed on-disk state of a [`DAG`](super::dag::DAG): its graph, the last committed
+/// round, the last committed authors, and the Merkle accumulator's leaves, in commit order.
+pub type DagSnapshot<N> =
+    (BTreeMap<u64, HashMap<Address<N>, AuthorCertificates<N>>>, u64, HashMap<Address<N>, u64>, Vec<Field<N>>);
+
+/// A pluggable, crash-recoverable persistence backend for the [`DAG`](super::dag::DAG).
+///
+/// Implementations are expected to write ahead every `insert` and `commit` so that, after a
+/// restart, `load` can reconstruct the graph, the last committed round, the last committed
+/// authors, and the Merkle accumulator's leaves exactly as they stood before the crash.
+pub trait DagStore<N: Network>: Send + Sync {
+    /// Persists a newly inserted certificate.
+    fn insert(&self, certificate: &BatchCertificate<N>) -> Result<()>;
+
+    /// Looks up an accepted certificate by its `certificate_id`, via the secondary index
+    /// maintained by `insert`. Returns `None` if no accepted certificate has that ID, including
+    /// if it is only known as equivocation evidence.
+    fn get_by_certificate_id(&self, certificate_id: Field<N>) -> Result<Option<BatchCertificate<N>>>;
+
+    /// Atomically persists a commit: appends `committed_certificate_id` to the persisted Merkle
+    /// accumulator, removes every certificate for `committed_author` at or below `committed_round`,
+    /// removes every certificate in a round at or below the GC horizon implied by `max_gc_rounds`
+    /// and `last_committed_round`, and updates the persisted commit state.
+    fn commit(
+        &self,
+        committed_author: Address<N>,
+        committed_round: u64,
+        committed_certificate_id: Field<N>,
+        max_gc_rounds: u64,
+        last_committed_round: u64,
+        last_committed_authors: &HashMap<Address<N>, u64>,
+    ) -> Result<()>;
+
+    /// Reconstructs the persisted graph, last committed round, last committed authors, and the
+    /// Merkle accumulator's leaves (in commit order).
+    fn load(&self) -> Result<DagSnapshot<N>>;
+}
+
+/// A no-op store, used when persistence is disabled (the default).
+/// Every write is discarded and `load` always returns an empty snapshot.
+#[derive(Clone, Debug, Default)]
+pub struct NoopDagStore;
+
+impl<N: Network> DagStore<N> for NoopDagStore {
+    fn insert(&self, _certificate: &BatchCertificate<N>) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_by_certificate_id(&self, _certificate_id: Field<N>) -> Result<Option<BatchCertificate<N>>> {
+        Ok(None)
+    }
+
+    fn commit(
+        &self,
+        _committed_author: Address<N>,
+        _committed_round: u64,
+        _committed_certificate_id: Field<N>,
+        _max_gc_rounds: u64,
+        _last_committed_round: u64,
+        _last_committed_authors: &HashMap<Address<N>, u64>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn load(&self) -> Result<DagSnapshot<N>> {
+        Ok((Default::default(), 0, Default::default(), Default::default()))
+    }
+}
+
+/// The column family that holds the accepted certificate for each `(round, author)`.
+const CERTIFICATES_CF: &str = "dag_certificates";
+/// The column family that secondary-indexes accepted certificates by `certificate_id` to their `(round, author)` key.
+const CERTIFICATE_IDS_CF: &str = "dag_certificate_ids";
+/// The column family that holds equivocating certificates, keyed by `certificate_id`, as slashing evidence.
+/// Kept separate from `CERTIFICATES_CF` so that a second certificate from the same author in the same
+/// round cannot overwrite the accepted certificate on disk.
+const CONFLICTS_CF: &str = "dag_certificate_conflicts";
+/// The column family that holds the Merkle accumulator's leaves, keyed by their commit index.
+const ACCUMULATOR_CF: &str = "dag_accumulator";
+/// The column family that holds the singleton commit state (`last_committed_round` and `last_committed_authors`).
+const META_CF: &str = "dag_meta";
+
+const META_KEY_LAST_COMMITTED_ROUND: &[u8] = b"last_committed_round";
+const META_KEY_LAST_COMMITTED_AUTHORS: &[u8] = b"last_committed_authors";
+const META_KEY_ACCUMULATOR_LEN: &[u8] = b"accumulator_len";
+
+/// A [`DagStore`] backed by RocksDB, write-ahead-logging every `insert` and `commit`.
+pub struct RocksDagStore<N: Network> {
+    db: rocksdb::DB,
+    _network: PhantomData<N>,
+}
+
+impl<N: Network> RocksDagStore<N> {
+    /// Opens (or creates) a RocksDB-backed store at the given `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut options = rocksdb::Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+
+        let db = rocksdb::DB::open_cf(
+            &options,
+            path,
+            [CERTIFICATES_CF, CERTIFICATE_IDS_CF, CONFLICTS_CF, ACCUMULATOR_CF, META_CF],
+        )?;
+        Ok(Self { db, _network: PhantomData })
+    }
+
+    /// Returns a handle to the given column family.
+    fn cf(&self, name: &str) -> Result<&rocksdb::ColumnFamily> {
+        self.db.cf_handle(name).ok_or_else(|| anyhow!("missing column family '{name}'"))
+    }
+
+    /// Encodes the `(round, author)` pair as a lexicographically round-ordered key.
+    fn certificate_key(round: u64, author: Address<N>) -> Result<Vec<u8>> {
+        let mut key = round.to_be_bytes().to_vec();
+        key.extend(author.to_bytes_le()?);
+        Ok(key)
+    }
+
+    /// Encodes a Merkle accumulator leaf's commit index as a lexicographically-ordered key.
+    fn accumulator_key(index: u64) -> [u8; 8] {
+        index.to_be_bytes()
+    }
+
+    /// Serializes the last committed authors map.
+    fn encode_last_committed_authors(last_committed_authors: &HashMap<Address<N>, u64>) -> Result<Vec<u8>> {
+        let mut bytes = (last_committed_authors.len() as u64).to_le_bytes().to_vec();
+        for (author, round) in last_committed_authors {
+            bytes.extend(author.to_bytes_le()?);
+            bytes.extend(round.to_le_bytes());
+        }
+        Ok(bytes)
+    }
+
+    /// Deserializes the last committed authors map.
+    fn decode_last_committed_authors(mut bytes: &[u8]) -> Result<HashMap<Address<N>, u64>> {
+        let count = u64::from_le_bytes(bytes[..8].try_into()?);
+        bytes = &bytes[8..];
+
+        let mut last_committed_authors = HashMap::new();
+        for _ in 0..count {
+            let author = Address::<N>::from_bytes_le(bytes)?;
+            bytes = &bytes[author.to_bytes_le()?.len()..];
+
+            let round = u64::from_le_bytes(bytes[..8].try_into()?);
+            bytes = &bytes[8..];
+
+            last_committed_authors.insert(author, round);
+        }
+        Ok(last_committed_authors)
+    }
+}
+
+impl<N: Network> DagStore<N> for RocksDagStore<N> {
+    fn insert(&self, certificate: &BatchCertificate<N>) -> Result<()> {
+        let key = Self::certificate_key(certificate.round(), certificate.author())?;
+        let certificates_cf = self.cf(CERTIFICATES_CF)?;
+
+        let mut batch = rocksdb::WriteBatch::default();
+        match self.db.get_cf(certificates_cf, &key)? {
+            // An accepted certificate already exists for this (round, author). If `certificate` is a
+            // distinct certificate, persist it as equivocation evidence rather than overwriting the
+            // accepted certificate - otherwise this is a re-insertion of the already-accepted certificate.
+            Some(existing)
+                if BatchCertificate::<N>::from_bytes_le(&existing)?.certificate_id() != certificate.certificate_id() =>
+            {
+                batch.put_cf(self.cf(CONFLICTS_CF)?, certificate.certificate_id().to_bytes_le()?, certificate.to_bytes_le()?);
+            }
+            _ => {
+                batch.put_cf(certificates_cf, &key, certificate.to_bytes_le()?);
+                batch.put_cf(self.cf(CERTIFICATE_IDS_CF)?, certificate.certificate_id().to_bytes_le()?, &key);
+            }
+        }
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    fn get_by_certificate_id(&self, certificate_id: Field<N>) -> Result<Option<BatchCertificate<N>>> {
+        let key = match self.db.get_cf(self.cf(CERTIFICATE_IDS_CF)?, certificate_id.to_bytes_le()?)? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        match self.db.get_cf(self.cf(CERTIFICATES_CF)?, key)? {
+            Some(bytes) => Ok(Some(BatchCertificate::<N>::from_bytes_le(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn commit(
+        &self,
+        committed_author: Address<N>,
+        committed_round: u64,
+        committed_certificate_id: Field<N>,
+        max_gc_rounds: u64,
+        last_committed_round: u64,
+        last_committed_authors: &HashMap<Address<N>, u64>,
+    ) -> Result<()> {
+        let certificates_cf = self.cf(CERTIFICATES_CF)?;
+        let certificate_ids_cf = self.cf(CERTIFICATE_IDS_CF)?;
+
+        let mut batch = rocksdb::WriteBatch::default();
+        for row in self.db.iterator_cf(certificates_cf, rocksdb::IteratorMode::Start) {
+            let (key, value) = row?;
+            let round = u64::from_be_bytes(key[..8].try_into()?);
+            let author = Address::<N>::from_bytes_le(&key[8..])?;
+
+            // Mirror `DAG::commit`'s GC semantics: drop rounds below the GC horizon, and drop any
+            // certificate for the committed author at or below the committed round.
+            let is_below_gc_horizon = round + max_gc_rounds <= last_committed_round;
+            let is_committed_authors_stale_round = author == committed_author && round <= committed_round;
+            if is_below_gc_horizon || is_committed_authors_stale_round {
+                let certificate = BatchCertificate::<N>::from_bytes_le(&value)?;
+                batch.delete_cf(certificates_cf, &key);
+                batch.delete_cf(certificate_ids_cf, certificate.certificate_id().to_bytes_le()?);
+            }
+        }
+
+        // Mirror the same GC semantics for equivocation evidence, keyed by `certificate_id` rather
+        // than `(round, author)`, so its round and author must be read from the decoded certificate.
+        let conflicts_cf = self.cf(CONFLICTS_CF)?;
+        for row in self.db.iterator_cf(conflicts_cf, rocksdb::IteratorMode::Start) {
+            let (key, value) = row?;
+            let certificate = BatchCertificate::<N>::from_bytes_le(&value)?;
+            let round = certificate.round();
+            let author = certificate.author();
+
+            let is_below_gc_horizon = round + max_gc_rounds <= last_committed_round;
+            let is_committed_authors_stale_round = author == committed_author && round <= committed_round;
+            if is_below_gc_horizon || is_committed_authors_stale_round {
+                batch.delete_cf(conflicts_cf, &key);
+            }
+        }
+
+        // Append the newly committed leaf to the persisted Merkle accumulator, so its root survives
+        // a restart even though the graph's GC removes the certificate itself.
+        let meta_cf = self.cf(META_CF)?;
+        let accumulator_len = match self.db.get_cf(meta_cf, META_KEY_ACCUMULATOR_LEN)? {
+            Some(bytes) => u64::from_le_bytes(bytes.as_slice().try_into()?),
+            None => 0,
+        };
+        batch.put_cf(self.cf(ACCUMULATOR_CF)?, Self::accumulator_key(accumulator_len), committed_certificate_id.to_bytes_le()?);
+        batch.put_cf(meta_cf, META_KEY_ACCUMULATOR_LEN, (accumulator_len + 1).to_le_bytes());
+
+        batch.put_cf(meta_cf, META_KEY_LAST_COMMITTED_ROUND, last_committed_round.to_le_bytes());
+        batch.put_cf(meta_cf, META_KEY_LAST_COMMITTED_AUTHORS, Self::encode_last_committed_authors(last_committed_authors)?);
+
+        // Commit the certificate removals and the updated commit state as a single atomic write.
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<DagSnapshot<N>> {
+        let mut graph: BTreeMap<u64, HashMap<Address<N>, AuthorCertificates<N>>> = BTreeMap::new();
+
+        // Each row in `CERTIFICATES_CF` is the sole accepted certificate for its `(round, author)` key,
+        // so every entry is freshly inserted here - equivocating certificates never reach this CF.
+        for row in self.db.iterator_cf(self.cf(CERTIFICATES_CF)?, rocksdb::IteratorMode::Start) {
+            let (_, value) = row?;
+            let certificate = BatchCertificate::<N>::from_bytes_le(&value)?;
+            let round = certificate.round();
+            let author = certificate.author();
+            graph.entry(round).or_default().insert(author, AuthorCertificates::new(certificate));
+        }
+
+        // Replay equivocation evidence alongside the accepted certificate it conflicts with.
+        for row in self.db.iterator_cf(self.cf(CONFLICTS_CF)?, rocksdb::IteratorMode::Start) {
+            let (_, value) = row?;
+            let certificate = BatchCertificate::<N>::from_bytes_le(&value)?;
+            if let Some(entry) = graph.get_mut(&certificate.round()).and_then(|authors| authors.get_mut(&certificate.author())) {
+                entry.record_conflict(certificate);
+            }
+        }
+
+        let last_committed_round = match self.db.get_cf(self.cf(META_CF)?, META_KEY_LAST_COMMITTED_ROUND)? {
+            Some(bytes) => u64::from_le_bytes(bytes.as_slice().try_into()?),
+            None => 0,
+        };
+        let last_committed_authors = match self.db.get_cf(self.cf(META_CF)?, META_KEY_LAST_COMMITTED_AUTHORS)? {
+            Some(bytes) => Self::decode_last_committed_authors(&bytes)?,
+            None => HashMap::new(),
+        };
+
+        // `ACCUMULATOR_CF` keys are big-endian commit indices, so iterating from the start yields
+        // the leaves back in their original commit order.
+        let mut accumulator_leaves = Vec::new();
+        for row in self.db.iterator_cf(self.cf(ACCUMULATOR_CF)?, rocksdb::IteratorMode::Start) {
+            let (_, value) = row?;
+            accumulator_leaves.push(Field::<N>::from_bytes_le(&value)?);
+        }
+
+        Ok((graph, last_committed_round, last_committed_authors, accumulator_leaves))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snarkvm::{
+        prelude::{Rng, TestRng},
+        utilities::Uniform,
+    };
+
+    type CurrentNetwork = snarkvm::prelude::Testnet3;
+
+    #[test]
+    fn test_last_committed_authors_roundtrip() {
+        let rng = &mut TestRng::default();
+
+        let mut last_committed_authors = HashMap::new();
+        last_committed_authors.insert(Address::<CurrentNetwork>::rand(rng), rng.gen::<u64>());
+        last_committed_authors.insert(Address::<CurrentNetwork>::rand(rng), rng.gen::<u64>());
+
+        let bytes = RocksDagStore::<CurrentNetwork>::encode_last_committed_authors(&last_committed_authors).unwrap();
+        let decoded = RocksDagStore::<CurrentNetwork>::decode_last_committed_authors(&bytes).unwrap();
+
+        assert_eq!(last_committed_authors, decoded);
+    }
+
+    #[test]
+    fn test_noop_store_loads_empty_snapshot() {
+        let store = NoopDagStore;
+        let (graph, last_committed_round, last_committed_authors, accumulator_leaves) =
+            DagStore::<CurrentNetwork>::load(&store).unwrap();
+
+        assert!(graph.is_empty());
+        assert_eq!(last_committed_round, 0);
+        assert!(last_committed_authors.is_empty());
+        assert!(accumulator_leaves.is_empty());
+    }
+}